@@ -8,13 +8,18 @@
 //!  - `Barrier` in this crate is `Clone`, and should *not* be wrapped in a `sync::Arc`.
 //!  - `Barrier::wait` in this crate takes a `&mut self` receiver as each thread must keep some
 //!    local state.
+//!  - [`Barrier::wait_async`] is available for waiting on the barrier from an async task instead
+//!    of blocking an OS thread.
+//!  - [`Barrier::wait_timeout`] gives up and reports a timeout if the rendezvous does not
+//!    complete within a given duration, instead of waiting forever.
 //!
-//! Furthermore, when a thread blocks on `Barrier::wait`, the thread will (currently) *never* be
-//! suspended, and instead spin on the barrier. For the first few spins, it will also not call
-//! `sched_yield` to avoid the cost of thread sleep/wakeup. If threads are expected to reach the
-//! barrier at nearly the same time, or barrier latency is critical, this is probably what you
-//! want. However, if barriers are staggered and far between, then you may want to use
-//! [`std::sync::Barrier`] instead, as it is better about handling long waits.
+//! By default, when a thread blocks on `Barrier::wait`, the thread will *never* be suspended, and
+//! instead spin on the barrier. For the first few spins, it will also not call `sched_yield` to
+//! avoid the cost of thread sleep/wakeup. If threads are expected to reach the barrier at nearly
+//! the same time, or barrier latency is critical, this is probably what you want. However, if
+//! barriers are staggered and far between, you may instead want a [`Barrier::new_parking`], which
+//! spins for a while and then parks the thread until the last arrival wakes it up, much like
+//! [`std::sync::Barrier`] does.
 //!
 //! # Examples
 //!
@@ -78,6 +83,8 @@
 //! [1]: https://dl.acm.org/citation.cfm?doid=103727.103729
 //! [2]: https://6xq.net/barrier-intro/
 //! [`std::sync::Barrier`]: https://doc.rust-lang.org/std/sync/struct.Barrier.html
+//! [`Barrier::wait_async`]: struct.Barrier.html#method.wait_async
+//! [`Barrier::wait_timeout`]: struct.Barrier.html#method.wait_timeout
 #![deny(missing_docs)]
 #![cfg_attr(feature = "nightly", feature(test))]
 
@@ -86,12 +93,208 @@ extern crate test;
 
 extern crate parking_lot_core;
 
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
 use std::sync::{atomic, Arc};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+struct WakerNode {
+    waker: Waker,
+    next: *mut WakerNode,
+}
+
+/// A small lock-free multi-producer/single-consumer stack of `Waker`s.
+///
+/// `wait_async` callers push themselves on with a CAS loop instead of taking a lock, and whoever
+/// ends up releasing the barrier drains the whole stack with a single atomic swap -- so the async
+/// path doesn't reintroduce the `Mutex` contention this crate otherwise avoids.
+struct WakerSet {
+    head: atomic::AtomicPtr<WakerNode>,
+}
+
+impl WakerSet {
+    fn new() -> Self {
+        WakerSet {
+            head: atomic::AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn register(&self, waker: Waker) {
+        let node = Box::into_raw(Box::new(WakerNode {
+            waker,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(atomic::Ordering::Acquire);
+        loop {
+            // Safety: `node` was just allocated above and has not yet been published, so we're
+            // the only ones who can be writing to it.
+            unsafe {
+                (*node).next = head;
+            }
+            match self.head.compare_exchange_weak(
+                head,
+                node,
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn wake_all(&self) {
+        let mut node = self.head.swap(ptr::null_mut(), atomic::Ordering::AcqRel);
+        while !node.is_null() {
+            // Safety: every node was pushed by `register` via `Box::into_raw`, and each node is
+            // popped off the stack (via the swap above, or a previous iteration) and freed
+            // exactly once.
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            boxed.waker.wake();
+        }
+    }
+}
+
+impl Drop for WakerSet {
+    fn drop(&mut self) {
+        let mut node = *self.head.get_mut();
+        while !node.is_null() {
+            // Safety: see `wake_all` -- we're just freeing, not waking, since nobody is left to
+            // care by the time the barrier itself is being dropped.
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+        }
+    }
+}
+
+// The top bit of the packed state word below holds the generation's sense, and the rest holds
+// the count of arrivals still outstanding for that generation. This halves the largest barrier
+// this crate can support (still far more participants than any real program has threads), in
+// exchange for making a release (resetting the count and flipping the sense) and a cancellation
+// (giving a slot back) each a single atomic compare-and-swap -- see
+// `BarrierInner::try_cancel_arrival` for why that matters.
+const SENSE_BIT: usize = 1 << (usize::BITS as usize - 1);
+
+fn pack(count: usize, sense: bool) -> usize {
+    debug_assert!(count & SENSE_BIT == 0, "too many outstanding arrivals to pack into one word");
+    if sense {
+        count | SENSE_BIT
+    } else {
+        count
+    }
+}
+
+fn unpack(state: usize) -> (usize, bool) {
+    (state & !SENSE_BIT, state & SENSE_BIT != 0)
+}
 
 struct BarrierInner {
-    gsense: atomic::AtomicBool,
-    count: atomic::AtomicUsize,
+    // packs the outstanding arrival count for the current generation together with that
+    // generation's sense, via `pack`/`unpack`, so the two always change together atomically --
+    // see `try_cancel_arrival`.
+    state: atomic::AtomicUsize,
     max: usize,
+    // wakers registered by tasks that are waiting on `Barrier::wait_async`, drained and woken by
+    // whichever thread or task turns out to be the last arrival for a generation.
+    wakers: WakerSet,
+}
+
+impl BarrierInner {
+    fn wake_async_waiters(&self) {
+        self.wakers.wake_all();
+    }
+
+    /// Returns whether the generation that releases at `lsense` has done so.
+    fn released(&self, lsense: bool) -> bool {
+        unpack(self.state.load(atomic::Ordering::SeqCst)).1 == lsense
+    }
+
+    /// Counts one arrival towards the generation that will release at `lsense`.
+    ///
+    /// Returns `true` if this was the last outstanding arrival, in which case `state` has already
+    /// been reset to `max` arrivals outstanding for the next generation and flipped to `lsense` as
+    /// part of the very same atomic update -- the caller is the leader and must release the
+    /// generation. Returns `false` otherwise, in which case the caller must wait for `lsense` to
+    /// be released by someone else.
+    fn arrive(&self, lsense: bool) -> bool {
+        let mut state = self.state.load(atomic::Ordering::SeqCst);
+        loop {
+            let (count, sense) = unpack(state);
+            let next = if count == 1 {
+                pack(self.max, lsense)
+            } else {
+                pack(count - 1, sense)
+            };
+            match self.state.compare_exchange_weak(
+                state,
+                next,
+                atomic::Ordering::SeqCst,
+                atomic::Ordering::SeqCst,
+            ) {
+                Ok(_) => return count == 1,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Attempts to undo a previously counted arrival for the generation that releases at
+    /// `lsense`, as when a waiter gives up on `wait_timeout` or a `wait_async` future is dropped
+    /// before it resolves.
+    ///
+    /// Returns `true` if the arrival was successfully undone, in which case the caller no longer
+    /// counts towards this generation and should flip its local sense back. Returns `false` if
+    /// the generation had already released using our slot by the time we tried to back out, in
+    /// which case `state` must be left untouched -- the caller should treat itself as having
+    /// completed the rendezvous normally instead.
+    ///
+    /// Because the count and sense live in the same word, there is no window where one has
+    /// changed without the other: either a snapshot already shows `lsense` released (and the
+    /// count that came with it), or our compare-and-swap against that exact snapshot will fail if
+    /// the leader races us, in which case we simply reread and notice the release next time
+    /// around. `before_cas` runs right before each compare-and-swap attempt, purely so tests can
+    /// deterministically land a concurrent release inside that window instead of relying on
+    /// timing.
+    fn try_cancel_arrival(&self, lsense: bool, before_cas: impl Fn()) -> bool {
+        let mut state = self.state.load(atomic::Ordering::SeqCst);
+        loop {
+            let (count, sense) = unpack(state);
+            if sense == lsense {
+                // the generation released using our slot after all -- `state` already reflects
+                // the next generation's arrival count, so there is nothing to give back.
+                return false;
+            }
+
+            before_cas();
+
+            match self.state.compare_exchange_weak(
+                state,
+                pack(count + 1, sense),
+                atomic::Ordering::SeqCst,
+                atomic::Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    fn cancel_arrival(&self, lsense: bool) -> bool {
+        self.try_cancel_arrival(lsense, || {})
+    }
+}
+
+/// The strategy a [`Barrier`] uses while a thread waits for the other participants to arrive.
+///
+/// [`Barrier`]: struct.Barrier.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Spin for as long as it takes for the barrier to release.
+    Spin,
+    /// Spin for a while, and then park the thread until it is woken by the last arrival.
+    Parking,
 }
 
 /// A barrier which enables multiple threads to synchronize the beginning of some computation.
@@ -99,6 +302,7 @@ pub struct Barrier {
     inner: Arc<BarrierInner>,
     lsense: bool,
     used: bool,
+    mode: Mode,
 }
 
 /// A `BarrierWaitResult` is returned by [`wait`] when all threads in the [`Barrier`]
@@ -115,7 +319,33 @@ pub struct Barrier {
 ///
 /// [`wait`]: struct.Barrier.html#method.wait
 /// [`Barrier`]: struct.Barrier.html
-pub struct BarrierWaitResult(bool);
+pub struct BarrierWaitResult {
+    leader: bool,
+    timed_out: bool,
+}
+
+impl BarrierWaitResult {
+    fn new_leader() -> Self {
+        BarrierWaitResult {
+            leader: true,
+            timed_out: false,
+        }
+    }
+
+    fn new_follower() -> Self {
+        BarrierWaitResult {
+            leader: false,
+            timed_out: false,
+        }
+    }
+
+    fn new_timed_out() -> Self {
+        BarrierWaitResult {
+            leader: false,
+            timed_out: true,
+        }
+    }
+}
 
 impl Barrier {
     /// Creates a new barrier that can block a given number of threads.
@@ -135,14 +365,48 @@ impl Barrier {
         Barrier {
             used: false,
             lsense: true,
+            mode: Mode::Spin,
             inner: Arc::new(BarrierInner {
-                gsense: atomic::AtomicBool::new(true),
-                count: atomic::AtomicUsize::new(n),
+                state: atomic::AtomicUsize::new(pack(n, true)),
                 max: n,
+                wakers: WakerSet::new(),
             }),
         }
     }
 
+    /// Creates a new barrier that can block a given number of threads, just like [`new`], but
+    /// which parks waiting threads instead of spinning forever.
+    ///
+    /// A thread that calls [`wait`] will spin for a bounded number of rounds first, so that
+    /// arrivals that are close together in time still pay (almost) no latency penalty. If the
+    /// barrier has not released by the time that bound is hit, the thread parks itself via
+    /// `parking_lot_core` instead of continuing to burn a core, and is woken up by the last
+    /// arriving thread. This makes the barrier behave more like [`std::sync::Barrier`] when
+    /// arrivals are staggered and far apart, while keeping the scalable counter-based core.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hurdles::Barrier;
+    /// let mut barrier = Barrier::new_parking(10);
+    /// ```
+    ///
+    /// [`new`]: struct.Barrier.html#method.new
+    /// [`wait`]: struct.Barrier.html#method.wait
+    /// [`std::sync::Barrier`]: https://doc.rust-lang.org/std/sync/struct.Barrier.html
+    pub fn new_parking(n: usize) -> Self {
+        Barrier {
+            mode: Mode::Parking,
+            ..Barrier::new(n)
+        }
+    }
+
+    /// An address that is stable for the lifetime of this barrier's generation counter, used as
+    /// the `parking_lot_core` key for parking and unparking waiters.
+    fn key(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
     /// Blocks the current thread until all threads have rendezvoused here.
     ///
     /// Barriers are re-usable after all threads have rendezvoused once, and can be used
@@ -181,23 +445,153 @@ impl Barrier {
     pub fn wait(&mut self) -> BarrierWaitResult {
         self.used = true;
         self.lsense = !self.lsense;
-        if self.inner.count.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+        if self.inner.arrive(self.lsense) {
             // we're the last to reach the barrier -- release all
-            self.inner
-                .count
-                .store(self.inner.max, atomic::Ordering::SeqCst);
-            self.inner
-                .gsense
-                .store(self.lsense, atomic::Ordering::SeqCst);
-            BarrierWaitResult(true)
+            if self.mode == Mode::Parking {
+                // wake up anyone who gave up spinning and parked themselves
+                unsafe {
+                    parking_lot_core::unpark_all(self.key(), parking_lot_core::DEFAULT_UNPARK_TOKEN);
+                }
+            }
+            // wake up anyone waiting via `wait_async` on this or another clone
+            self.inner.wake_async_waiters();
+            BarrierWaitResult::new_leader()
         } else {
             // wait for everyone to reach the barrier
             let mut wait = parking_lot_core::SpinWait::new();
-            while self.inner.gsense.load(atomic::Ordering::SeqCst) != self.lsense {
-                // XXX: in theory we could go even further and park the thread eventually
-                wait.spin();
+            while !self.inner.released(self.lsense) {
+                if self.mode == Mode::Spin || wait.spin() {
+                    continue;
+                }
+
+                // we've spun for a while with no luck -- park until the leader wakes us up
+                let lsense = self.lsense;
+                let inner = &self.inner;
+                unsafe {
+                    parking_lot_core::park(
+                        self.key(),
+                        || !inner.released(lsense),
+                        || {},
+                        |_, _| {},
+                        parking_lot_core::DEFAULT_PARK_TOKEN,
+                        None,
+                    );
+                }
+            }
+            BarrierWaitResult::new_follower()
+        }
+    }
+
+    /// Returns a [`Future`] that resolves once all threads have rendezvoused here.
+    ///
+    /// This behaves just like [`wait`], except that instead of blocking the calling thread, it
+    /// yields a future that an async executor can poll, registering a [`Waker`] instead of
+    /// spinning or parking while the barrier is awaited. As with [`wait`], exactly one of the
+    /// resolved [`BarrierWaitResult`]s will report [`is_leader`].
+    ///
+    /// If the returned future is dropped before it resolves (for example because the enclosing
+    /// task was cancelled), this thread's arrival is undone so the remaining participants are not
+    /// left waiting for a generation that can never complete.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hurdles::Barrier;
+    ///
+    /// # async fn go() {
+    /// let mut barrier = Barrier::new(1);
+    /// let barrier_wait_result = barrier.wait_async().await;
+    /// # }
+    /// ```
+    ///
+    /// [`Future`]: https://doc.rust-lang.org/std/future/trait.Future.html
+    /// [`Waker`]: https://doc.rust-lang.org/std/task/struct.Waker.html
+    /// [`wait`]: struct.Barrier.html#method.wait
+    /// [`BarrierWaitResult`]: struct.BarrierWaitResult.html
+    /// [`is_leader`]: struct.BarrierWaitResult.html#method.is_leader
+    pub fn wait_async(&mut self) -> BarrierWaitFuture<'_> {
+        BarrierWaitFuture {
+            barrier: self,
+            arrived: false,
+            done: false,
+        }
+    }
+
+    /// Blocks the current thread until all threads have rendezvoused here, or until `dur` has
+    /// elapsed.
+    ///
+    /// This behaves just like [`wait`], except that if `dur` elapses before the rendezvous
+    /// completes, this thread gives up waiting and returns a [`BarrierWaitResult`] that reports
+    /// [`timed_out`]. Giving up re-increments the barrier's arrival counter, so the barrier
+    /// arithmetic stays correct for the remaining participants -- a thread that times out no
+    /// longer counts towards the current generation, and a later call to [`wait`] or
+    /// [`wait_timeout`] arrives at that same generation again if it has not released yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hurdles::Barrier;
+    /// use std::time::Duration;
+    ///
+    /// let mut barrier = Barrier::new(2);
+    /// let barrier_wait_result = barrier.wait_timeout(Duration::from_millis(10));
+    /// assert_eq!(barrier_wait_result.timed_out(), true);
+    /// ```
+    ///
+    /// [`wait`]: struct.Barrier.html#method.wait
+    /// [`wait_timeout`]: struct.Barrier.html#method.wait_timeout
+    /// [`BarrierWaitResult`]: struct.BarrierWaitResult.html
+    /// [`timed_out`]: struct.BarrierWaitResult.html#method.timed_out
+    pub fn wait_timeout(&mut self, dur: Duration) -> BarrierWaitResult {
+        self.used = true;
+        self.lsense = !self.lsense;
+        if self.inner.arrive(self.lsense) {
+            // we're the last to reach the barrier -- release all
+            if self.mode == Mode::Parking {
+                unsafe {
+                    parking_lot_core::unpark_all(self.key(), parking_lot_core::DEFAULT_UNPARK_TOKEN);
+                }
+            }
+            self.inner.wake_async_waiters();
+            return BarrierWaitResult::new_leader();
+        }
+
+        let deadline = Instant::now() + dur;
+        let mut wait = parking_lot_core::SpinWait::new();
+        loop {
+            if self.inner.released(self.lsense) {
+                return BarrierWaitResult::new_follower();
+            }
+
+            if Instant::now() >= deadline {
+                // give our slot back -- the remaining participants shouldn't be stuck waiting
+                // for an arrival that isn't coming. If the generation actually released
+                // concurrently with our decision to give up, `cancel_arrival` leaves `state`
+                // alone and we report a normal rendezvous instead of a timeout.
+                if !self.inner.cancel_arrival(self.lsense) {
+                    return BarrierWaitResult::new_follower();
+                }
+                self.lsense = !self.lsense;
+                return BarrierWaitResult::new_timed_out();
+            }
+
+            if self.mode == Mode::Spin || wait.spin() {
+                continue;
+            }
+
+            // we've spun for a while with no luck -- park until woken or the deadline passes
+            let lsense = self.lsense;
+            let inner = &self.inner;
+            unsafe {
+                parking_lot_core::park(
+                    self.key(),
+                    || !inner.released(lsense),
+                    || {},
+                    |_, _| {},
+                    parking_lot_core::DEFAULT_PARK_TOKEN,
+                    Some(deadline),
+                );
             }
-            BarrierWaitResult(false)
         }
     }
 }
@@ -208,11 +602,86 @@ impl Clone for Barrier {
         Barrier {
             used: false,
             lsense: self.lsense,
+            mode: self.mode,
             inner: self.inner.clone(),
         }
     }
 }
 
+/// A [`Future`] returned by [`Barrier::wait_async`] that resolves once all participants have
+/// rendezvoused.
+///
+/// [`Future`]: https://doc.rust-lang.org/std/future/trait.Future.html
+/// [`Barrier::wait_async`]: struct.Barrier.html#method.wait_async
+pub struct BarrierWaitFuture<'a> {
+    barrier: &'a mut Barrier,
+    // whether we've already counted ourselves as arrived for this generation
+    arrived: bool,
+    // whether we've resolved -- if we haven't, and we arrived, `Drop` must undo the arrival
+    done: bool,
+}
+
+impl<'a> Future for BarrierWaitFuture<'a> {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = Pin::into_inner(self);
+        let inner = &this.barrier.inner;
+
+        if !this.arrived {
+            this.barrier.used = true;
+            this.barrier.lsense = !this.barrier.lsense;
+            this.arrived = true;
+
+            if inner.arrive(this.barrier.lsense) {
+                // we're the last to reach the barrier -- release all
+                if this.barrier.mode == Mode::Parking {
+                    unsafe {
+                        parking_lot_core::unpark_all(
+                            this.barrier.key(),
+                            parking_lot_core::DEFAULT_UNPARK_TOKEN,
+                        );
+                    }
+                }
+                inner.wake_async_waiters();
+                this.done = true;
+                return Poll::Ready(BarrierWaitResult::new_leader());
+            }
+        }
+
+        if inner.released(this.barrier.lsense) {
+            this.done = true;
+            return Poll::Ready(BarrierWaitResult::new_follower());
+        }
+
+        // register our waker before re-checking, so we can't miss a wakeup that happens between
+        // the check above and the registration below
+        inner.wakers.register(cx.waker().clone());
+
+        if inner.released(this.barrier.lsense) {
+            this.done = true;
+            return Poll::Ready(BarrierWaitResult::new_follower());
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for BarrierWaitFuture<'a> {
+    fn drop(&mut self) {
+        if self.arrived
+            && !self.done
+            && self.barrier.inner.cancel_arrival(self.barrier.lsense)
+        {
+            // we counted ourselves as arrived, but never observed the release, and the
+            // generation hadn't already completed using our slot -- give it back so the other
+            // participants aren't left waiting for an arrival that this task will never
+            // complete.
+            self.barrier.lsense = !self.barrier.lsense;
+        }
+    }
+}
+
 impl BarrierWaitResult {
     /// Returns whether this thread from [`wait`] is the "leader thread".
     ///
@@ -231,16 +700,65 @@ impl BarrierWaitResult {
     /// assert_eq!(barrier_wait_result.is_leader(), true);
     /// ```
     pub fn is_leader(&self) -> bool {
-        self.0
+        self.leader
+    }
+
+    /// Returns whether this thread gave up waiting in [`wait_timeout`] before the barrier
+    /// released, rather than completing the rendezvous.
+    ///
+    /// A timed-out result is never also the leader -- if the call to [`wait_timeout`] that
+    /// reports the timeout was actually the last arrival, the barrier has released, and the
+    /// result will report [`is_leader`] instead.
+    ///
+    /// [`wait_timeout`]: struct.Barrier.html#method.wait_timeout
+    /// [`is_leader`]: struct.BarrierWaitResult.html#method.is_leader
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hurdles::Barrier;
+    /// use std::time::Duration;
+    ///
+    /// let mut barrier = Barrier::new(2);
+    /// let barrier_wait_result = barrier.wait_timeout(Duration::from_millis(10));
+    /// assert_eq!(barrier_wait_result.timed_out(), true);
+    /// ```
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Barrier;
+    use std::future::Future;
     use std::sync::mpsc::{channel, TryRecvError};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
     use std::thread;
 
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    // a minimal executor that parks the calling thread between polls, just enough to exercise
+    // `Barrier::wait_async` without pulling in an async runtime as a dev-dependency.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = Box::pin(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(val) => return val,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
     #[cfg(feature = "nightly")]
     use test::Bencher;
 
@@ -305,4 +823,205 @@ mod tests {
         }
         assert!(leader_found);
     }
+
+    #[test]
+    fn test_barrier_async() {
+        const N: usize = 10;
+
+        let mut barrier = Barrier::new(N);
+        let (tx, rx) = channel();
+
+        for _ in 0..N - 1 {
+            let mut c = barrier.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                tx.send(block_on(c.wait_async()).is_leader()).unwrap();
+            });
+        }
+
+        // At this point, all spawned threads should be blocked,
+        // so we shouldn't get anything from the port
+        assert!(match rx.try_recv() {
+            Err(TryRecvError::Empty) => true,
+            _ => false,
+        });
+
+        let mut leader_found = block_on(barrier.wait_async()).is_leader();
+
+        // Now, the barrier is cleared and we should get data.
+        for _ in 0..N - 1 {
+            if rx.recv().unwrap() {
+                assert!(!leader_found);
+                leader_found = true;
+            }
+        }
+        assert!(leader_found);
+    }
+
+    #[test]
+    fn test_barrier_wait_async_drop_races_completion() {
+        use std::time::Duration;
+
+        // Exercise the window where a `wait_async` future is dropped (e.g. its task was
+        // cancelled) right as the other participant arrives and completes the generation using
+        // our slot. Varying the racing thread's delay nudges the two over the race a good
+        // fraction of the time; regardless of which way it falls, the barrier must stay usable.
+        for i in 0..500u64 {
+            let mut barrier = Barrier::new(2);
+            let mut c = barrier.clone();
+            let (tx, rx) = channel();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_micros(i % 7));
+                tx.send(c.wait().is_leader()).unwrap();
+            });
+
+            let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            let lsense_before = barrier.lsense;
+            let mut fut = Box::pin(barrier.wait_async());
+            let polled = fut.as_mut().poll(&mut cx);
+            drop(fut);
+
+            if let Poll::Ready(result) = polled {
+                // we completed the rendezvous on the very first poll, before there was anything
+                // to drop.
+                let racer_leader = rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("barrier deadlocked after a wait_async drop race");
+                assert_ne!(result.is_leader(), racer_leader);
+                continue;
+            }
+
+            if barrier.lsense != lsense_before {
+                // the future was dropped while still pending, but the racing thread completed the
+                // generation using our slot before the drop could give it back, so our arrival
+                // stands and the racer was the leader.
+                let racer_leader = rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("barrier deadlocked after a wait_async drop race");
+                assert!(racer_leader);
+                continue;
+            }
+
+            // the future was dropped while still pending and our arrival was undone, so the
+            // racing thread is still waiting for it -- supply it ourselves.
+            let leader_found = barrier.wait().is_leader();
+            let racer_leader = rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("barrier deadlocked after a wait_async drop race");
+            assert_ne!(leader_found, racer_leader);
+        }
+    }
+
+    #[test]
+    fn test_barrier_wait_timeout() {
+        use std::time::Duration;
+
+        let mut barrier = Barrier::new(2);
+        let mut c = barrier.clone();
+
+        // only one of the two expected arrivals shows up, so we should time out rather than
+        // wait forever.
+        let result = barrier.wait_timeout(Duration::from_millis(10));
+        assert!(!result.is_leader());
+        assert!(result.timed_out());
+
+        // the timed-out thread gave its slot back, so the barrier should still complete that
+        // same generation once everyone actually arrives.
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            tx.send(c.wait().is_leader()).unwrap();
+        });
+
+        let leader_found = barrier.wait().is_leader();
+        assert_ne!(leader_found, rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_barrier_wait_timeout_races_completion() {
+        use std::time::Duration;
+
+        // Exercise the window where a `wait_timeout` caller decides it has timed out right as
+        // the other participant arrives and completes the generation using its slot. Varying the
+        // racing thread's delay across iterations nudges the two over the race a good fraction of
+        // the time; regardless of which way it falls, the barrier must come out consistent.
+        for i in 0..500u64 {
+            let mut barrier = Barrier::new(2);
+            let mut c = barrier.clone();
+            let (tx, rx) = channel();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_micros(i % 7));
+                tx.send(c.wait().is_leader()).unwrap();
+            });
+
+            let result = barrier.wait_timeout(Duration::from_micros(10));
+            if result.timed_out() {
+                // we gave our slot back, so the racing thread is still waiting for it -- supply
+                // it so the generation actually completes instead of leaving the racer hanging.
+                let leader_found = barrier.wait().is_leader();
+                let racer_leader = rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("barrier deadlocked after a wait_timeout race");
+                assert_ne!(leader_found, racer_leader);
+            } else {
+                // we completed the rendezvous after all, despite racing the deadline.
+                let racer_leader = rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("barrier deadlocked after a wait_timeout race");
+                assert_ne!(result.is_leader(), racer_leader);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel_arrival_is_atomic_with_release() {
+        use std::sync::mpsc::sync_channel;
+
+        // Deliberately target the count-vs-sense window a torn two-atomic release used to leave
+        // open: pause a cancellation right before its compare-and-swap (via `try_cancel_arrival`'s
+        // test-only hook) and let the other participant complete the generation using our slot
+        // while we're paused there, instead of hoping a sleep lands in the gap by luck.
+        let mut barrier = Barrier::new(2);
+        let mut b = barrier.clone();
+
+        // manually perform the "arrived, about to give up" half of what `wait_timeout` and a
+        // dropped `wait_async` future do, without racing either against a timer.
+        barrier.used = true;
+        let lsense = !barrier.lsense;
+        barrier.lsense = lsense;
+        assert!(!barrier.inner.arrive(lsense));
+
+        let (paused_tx, paused_rx) = sync_channel::<()>(0);
+        let (go_tx, go_rx) = sync_channel::<()>(0);
+        let inner = barrier.inner.clone();
+        let canceller = thread::spawn(move || {
+            inner.try_cancel_arrival(lsense, || {
+                paused_tx.send(()).unwrap();
+                go_rx.recv().unwrap();
+            })
+        });
+
+        // block until the canceller is paused right before its compare-and-swap, then let the
+        // other participant release the generation using the slot the canceller is trying to give
+        // back.
+        paused_rx.recv().unwrap();
+        assert!(b.wait().is_leader());
+        go_tx.send(()).unwrap();
+
+        let cancelled = canceller.join().expect("cancelling thread panicked");
+        assert!(
+            !cancelled,
+            "the generation released using our slot while cancellation was paused, so it must \
+             report failure instead of corrupting the arrival count"
+        );
+
+        // the barrier must still work correctly for a later generation with the same
+        // participants -- the race above must not have leaked or double-counted a slot.
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            tx.send(b.wait().is_leader()).unwrap();
+        });
+        let leader_found = barrier.wait().is_leader();
+        assert_ne!(leader_found, rx.recv().unwrap());
+    }
 }